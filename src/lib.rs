@@ -1,12 +1,33 @@
+use log::kv::{Error as KvError, Key, VisitSource, Value};
 use log::{Level, Log, Metadata, Record};
-use std::cell::RefCell;
-use std::sync::Once;
+use regex::Regex;
+use std::cell::{Cell, RefCell};
+use std::collections::VecDeque;
+use std::rc::Rc;
+use std::sync::{Arc, Mutex, Once};
+use std::thread;
+use std::time::{Duration, Instant};
 
 static LOGGER: TestLogger = TestLogger;
 static LOG_INIT_ONCE: Once = Once::new();
-thread_local!(static LOG_RECORDS: RefCell<Vec<CapRecord>> = RefCell::new(Vec::new()));
 
-#[derive(Debug, Clone, PartialEq, Eq)]
+// Every non-shared `CapLog` alive on this thread, in creation order. Each
+// gets its own buffer/capacity/level filter rather than all instances on a
+// thread clobbering one ambient slot, so overlapping `CapLog`s on the same
+// thread (e.g. one `with_capacity` nested inside another, or a filtered
+// `builder()` alongside a plain `new()`) don't stomp on each other's state.
+thread_local!(static INSTANCES: RefCell<Vec<Rc<Instance>>> = const { RefCell::new(Vec::new()) });
+
+type SharedRecords = Arc<Mutex<Vec<CapRecord>>>;
+
+// The shared sink this thread participates in, if any. Set by
+// `CapLog::new_shared` on its own thread, and propagated to worker threads
+// spawned via `CapLog::spawn`. A thread with no entry here writes to its own
+// `INSTANCES` entries as usual, so unrelated threads (and the tests running
+// on them) are never affected by another thread's shared capture session.
+thread_local!(static SHARED_SINK: RefCell<Option<SharedRecords>> = const { RefCell::new(None) });
+
+#[derive(Debug, Clone)]
 pub struct CapRecord {
     pub level: Level,
     pub target: String,
@@ -14,10 +35,73 @@ pub struct CapRecord {
     pub module_path: Option<String>,
     pub file: Option<String>,
     pub line: Option<u32>,
+    pub fields: Vec<(String, String)>,
+    pub timestamp: Instant,
+}
+
+// `timestamp` is when the record was captured, not part of its logical
+// content, so it's excluded from equality (tests would otherwise be unable
+// to assert on a freshly-captured `CapRecord` with a literal).
+impl PartialEq for CapRecord {
+    fn eq(&self, other: &Self) -> bool {
+        self.level == other.level
+            && self.target == other.target
+            && self.msg == other.msg
+            && self.module_path == other.module_path
+            && self.file == other.file
+            && self.line == other.line
+            && self.fields == other.fields
+    }
+}
+
+impl Eq for CapRecord {}
+
+impl CapRecord {
+    /// Returns `true` if a structured field with the given key was captured.
+    pub fn has_field(&self, key: &str) -> bool {
+        self.fields.iter().any(|(k, _)| k == key)
+    }
+
+    /// Returns the value of the structured field with the given key, if any.
+    pub fn field(&self, key: &str) -> Option<&str> {
+        self.fields
+            .iter()
+            .find(|(k, _)| k == key)
+            .map(|(_, v)| v.as_str())
+    }
+
+    /// Rough estimate of this record's memory footprint: the sum of its
+    /// string lengths plus a fixed overhead for the rest of the struct.
+    fn approx_size(&self) -> usize {
+        const FIXED_OVERHEAD: usize = 64;
+
+        self.target.len()
+            + self.msg.len()
+            + self.module_path.as_deref().map_or(0, str::len)
+            + self.file.as_deref().map_or(0, str::len)
+            + self
+                .fields
+                .iter()
+                .map(|(k, v)| k.len() + v.len())
+                .sum::<usize>()
+            + FIXED_OVERHEAD
+    }
+}
+
+struct FieldVisitor<'a>(&'a mut Vec<(String, String)>);
+
+impl<'kvs> VisitSource<'kvs> for FieldVisitor<'_> {
+    fn visit_pair(&mut self, key: Key<'kvs>, value: Value<'kvs>) -> Result<(), KvError> {
+        self.0.push((key.to_string(), value.to_string()));
+        Ok(())
+    }
 }
 
 impl From<&Record<'_>> for CapRecord {
     fn from(r: &Record) -> Self {
+        let mut fields = Vec::new();
+        let _ = r.key_values().visit(&mut FieldVisitor(&mut fields));
+
         Self {
             level: r.metadata().level(),
             target: r.metadata().target().to_owned(),
@@ -25,107 +109,679 @@ impl From<&Record<'_>> for CapRecord {
             module_path: r.module_path().map(|s| s.to_string()),
             file: r.file().map(|s| s.to_string()),
             line: r.line(),
+            fields,
+            timestamp: Instant::now(),
+        }
+    }
+}
+
+/// A declarative, composable alternative to [`CapLog::find`]'s closures.
+///
+/// Criteria are combined with AND semantics and evaluated cheapest-first
+/// (timestamp, then level, then target, then regex) so a narrow filter
+/// doesn't pay for a regex match it didn't need.
+#[derive(Debug, Default, Clone)]
+pub struct RecordFilter {
+    not_before: Option<Instant>,
+    level: Option<Level>,
+    target: Option<String>,
+    regex: Option<Regex>,
+    limit: Option<usize>,
+}
+
+impl RecordFilter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Keep only records captured at or after the given instant.
+    pub fn not_before(mut self, not_before: Instant) -> Self {
+        self.not_before = Some(not_before);
+        self
+    }
+
+    /// Keep only records at or above the given severity.
+    pub fn level(mut self, level: Level) -> Self {
+        self.level = Some(level);
+        self
+    }
+
+    /// Keep only records whose target contains the given substring.
+    pub fn target(mut self, target: impl Into<String>) -> Self {
+        self.target = Some(target.into());
+        self
+    }
+
+    /// Keep only records whose message matches the given regex.
+    pub fn regex(mut self, regex: Regex) -> Self {
+        self.regex = Some(regex);
+        self
+    }
+
+    /// Stop collecting once this many records have matched.
+    pub fn limit(mut self, limit: usize) -> Self {
+        self.limit = Some(limit);
+        self
+    }
+
+    fn matches(&self, record: &CapRecord) -> bool {
+        if let Some(not_before) = self.not_before
+            && record.timestamp < not_before
+        {
+            return false;
+        }
+
+        if let Some(level) = self.level
+            && record.level > level
+        {
+            return false;
+        }
+
+        if let Some(target) = &self.target
+            && !record.target.contains(target.as_str())
+        {
+            return false;
+        }
+
+        if let Some(regex) = &self.regex
+            && !regex.is_match(&record.msg)
+        {
+            return false;
         }
+
+        true
+    }
+}
+
+/// How the capture buffer is bounded, set via [`CapLog::with_capacity`] or
+/// [`CapLog::with_byte_capacity`]. Oldest records are evicted first.
+#[derive(Debug, Clone, Copy)]
+enum Capacity {
+    Records(usize),
+    Bytes(usize),
+}
+
+impl Capacity {
+    /// Evicts oldest-first until `records` is back within budget. The
+    /// `Bytes` budget is tracked via `byte_total`, a running total the
+    /// caller keeps in sync on every push/eviction, so this doesn't need to
+    /// re-sum the whole buffer on every call.
+    fn evict(self, records: &mut VecDeque<CapRecord>, byte_total: &Cell<usize>) {
+        match self {
+            Capacity::Records(max) => {
+                while records.len() > max {
+                    records.pop_front();
+                }
+            }
+            Capacity::Bytes(max) => {
+                while byte_total.get() > max {
+                    let Some(evicted) = records.pop_front() else {
+                        break;
+                    };
+                    byte_total.set(byte_total.get() - evicted.approx_size());
+                }
+            }
+        }
+    }
+}
+
+/// Per-[`CapLog`]-instance capture state. Each non-shared `CapLog` owns one
+/// of these instead of all instances on a thread sharing one ambient
+/// buffer/capacity/level filter, so creating or dropping one `CapLog` can't
+/// clobber another's configuration.
+struct Instance {
+    records: RefCell<VecDeque<CapRecord>>,
+    byte_total: Cell<usize>,
+    capacity: Cell<Option<Capacity>>,
+    level_config: RefCell<LevelConfig>,
+}
+
+/// Polling interval used by [`CapLog::wait_for`] and [`CapLog::wait_for_count`]
+/// (and their `_async` counterparts).
+const POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+/// Returned by the `wait_for*` methods when `timeout` elapses before the
+/// expected records showed up.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TimeoutError;
+
+impl std::fmt::Display for TimeoutError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "timed out waiting for expected log records")
+    }
+}
+
+impl std::error::Error for TimeoutError {}
+
+/// Ingestion-time level filtering: a global minimum level plus optional
+/// per-target overrides, set via [`CapLog::builder`].
+#[derive(Debug, Clone)]
+struct LevelConfig {
+    global: log::LevelFilter,
+    target_levels: Vec<(String, log::LevelFilter)>,
+}
+
+impl Default for LevelConfig {
+    fn default() -> Self {
+        Self {
+            global: log::LevelFilter::Trace,
+            target_levels: Vec::new(),
+        }
+    }
+}
+
+impl LevelConfig {
+    fn allows(&self, level: Level, target: &str) -> bool {
+        let threshold = self
+            .target_levels
+            .iter()
+            .find(|(t, _)| target.starts_with(t.as_str()))
+            .map_or(self.global, |(_, level)| *level);
+
+        level <= threshold
+    }
+}
+
+/// Builds a [`CapLog`] with ingestion-time level/target filtering, so noisy
+/// dependencies never make it into the capture buffer in the first place.
+///
+/// ```ignore
+/// let caplog = CapLog::builder()
+///     .level(LevelFilter::Info)
+///     .target_level("hyper", LevelFilter::Off)
+///     .build();
+/// ```
+#[derive(Debug, Default)]
+pub struct CapLogBuilder {
+    config: LevelConfig,
+}
+
+impl CapLogBuilder {
+    fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the global minimum level to capture. Defaults to [`log::LevelFilter::Trace`].
+    pub fn level(mut self, level: log::LevelFilter) -> Self {
+        self.config.global = level;
+        self
+    }
+
+    /// Overrides the minimum level for targets starting with `target`.
+    pub fn target_level(mut self, target: impl Into<String>, level: log::LevelFilter) -> Self {
+        self.config.target_levels.push((target.into(), level));
+        self
+    }
+
+    pub fn build(self) -> CapLog {
+        CapLog::init_logger();
+
+        CapLog::with_capacity_inner(self.config, None)
     }
 }
 
 struct TestLogger;
 
 impl Log for TestLogger {
-    /// This logger is always enabled, in order to ensure we record everything.
+    /// This logger is always enabled, in order to ensure we record everything;
+    /// ingestion-time filtering happens in `log` instead, per [`LevelConfig`].
     fn enabled(&self, _: &Metadata) -> bool {
         true
     }
 
     fn log(&self, record: &Record) {
-        LOG_RECORDS.with(|records| records.borrow_mut().push(record.into()))
+        let shared = SHARED_SINK.with(|s| s.borrow().clone());
+        if let Some(sink) = shared {
+            sink.lock().unwrap().push(record.into());
+            return;
+        }
+
+        INSTANCES.with(|instances| {
+            let instances = instances.borrow();
+            if instances.is_empty() {
+                return;
+            }
+
+            let record: CapRecord = record.into();
+
+            for instance in instances.iter() {
+                let allowed = instance
+                    .level_config
+                    .borrow()
+                    .allows(record.level, &record.target);
+                if !allowed {
+                    continue;
+                }
+
+                let mut records = instance.records.borrow_mut();
+                instance
+                    .byte_total
+                    .set(instance.byte_total.get() + record.approx_size());
+                records.push_back(record.clone());
+
+                if let Some(capacity) = instance.capacity.get() {
+                    capacity.evict(&mut records, &instance.byte_total);
+                }
+            }
+        });
     }
 
     fn flush(&self) {}
 }
 
-pub struct CapLog {}
+pub struct CapLog {
+    shared: Option<SharedRecords>,
+    instance: Option<Rc<Instance>>,
+}
 
 impl CapLog {
     #[allow(clippy::new_without_default)]
     pub fn new() -> Self {
+        Self::init_logger();
+
+        Self::with_capacity_inner(LevelConfig::default(), None)
+    }
+
+    /// Starts a [`CapLogBuilder`] for configuring ingestion-time level and
+    /// target filtering.
+    pub fn builder() -> CapLogBuilder {
+        CapLogBuilder::new()
+    }
+
+    /// Like [`CapLog::new`], but captures records emitted from this thread
+    /// and any thread spawned via [`CapLog::spawn`] into a shared buffer
+    /// instead of the default per-thread one.
+    ///
+    /// This is for tests that exercise code logging from a worker thread,
+    /// where the per-thread capture would never see the records. Other
+    /// threads (including other tests' `CapLog` sessions) are unaffected,
+    /// since participation is opt-in per thread rather than process-wide.
+    ///
+    /// Only threads spawned via [`CapLog::spawn`] participate. A raw
+    /// `std::thread::spawn`, or worker threads you don't control the
+    /// spawning of (e.g. a tokio runtime's thread pool), never set up the
+    /// shared sink and their records are silently not captured.
+    pub fn new_shared() -> Self {
+        Self::init_logger();
+
+        let sink: SharedRecords = Arc::new(Mutex::new(Vec::new()));
+        SHARED_SINK.with(|s| *s.borrow_mut() = Some(sink.clone()));
+
+        Self {
+            shared: Some(sink),
+            instance: None,
+        }
+    }
+
+    /// Spawns a thread that participates in this `CapLog`'s shared capture
+    /// session, if it has one. Use this instead of `std::thread::spawn` when
+    /// testing code that logs from worker threads, so those records show up
+    /// in this `CapLog`'s `find`/`get_all`/`wait_for`/etc.
+    ///
+    /// With a non-shared `CapLog` (e.g. from [`CapLog::new`]), this just
+    /// spawns a plain thread; records it logs are not seen by this `CapLog`,
+    /// and are captured only by a `CapLog` created on that thread.
+    ///
+    /// This only covers threads spawned through this method. Code that logs
+    /// from a thread it spawned itself (a raw `std::thread::spawn`, or a
+    /// runtime's own worker pool, such as tokio's) is not routed to the
+    /// shared sink; there's no hook here for threads this crate doesn't
+    /// spawn.
+    pub fn spawn<F, T>(&self, f: F) -> thread::JoinHandle<T>
+    where
+        F: FnOnce() -> T + Send + 'static,
+        T: Send + 'static,
+    {
+        let sink = self.shared.clone();
+
+        thread::spawn(move || {
+            if let Some(sink) = sink {
+                SHARED_SINK.with(|s| *s.borrow_mut() = Some(sink));
+            }
+
+            f()
+        })
+    }
+
+    /// Like [`CapLog::new`], but evicts the oldest record once more than
+    /// `max_records` have been captured. Unbounded by default.
+    pub fn with_capacity(max_records: usize) -> Self {
+        Self::init_logger();
+
+        Self::with_capacity_inner(LevelConfig::default(), Some(Capacity::Records(max_records)))
+    }
+
+    /// Like [`CapLog::new`], but evicts the oldest records once the capture
+    /// buffer's estimated size exceeds `max_bytes`. Unbounded by default.
+    pub fn with_byte_capacity(max_bytes: usize) -> Self {
+        Self::init_logger();
+
+        Self::with_capacity_inner(LevelConfig::default(), Some(Capacity::Bytes(max_bytes)))
+    }
+
+    /// Registers a new per-instance [`Instance`] on this thread's
+    /// [`INSTANCES`] list and returns the `CapLog` owning it.
+    fn with_capacity_inner(level_config: LevelConfig, capacity: Option<Capacity>) -> Self {
+        let instance = Rc::new(Instance {
+            records: RefCell::new(VecDeque::new()),
+            byte_total: Cell::new(0),
+            capacity: Cell::new(capacity),
+            level_config: RefCell::new(level_config),
+        });
+
+        INSTANCES.with(|instances| instances.borrow_mut().push(instance.clone()));
+
+        Self {
+            shared: None,
+            instance: Some(instance),
+        }
+    }
+
+    fn init_logger() {
         LOG_INIT_ONCE.call_once(|| {
             log::set_logger(&LOGGER)
                 .map(|()| log::set_max_level(log::LevelFilter::Trace))
                 .expect("Error initializing test logger")
         });
-
-        Self {}
     }
 
     pub fn get_all(&self) -> Vec<CapRecord> {
-        LOG_RECORDS.with(|records| records.borrow().iter().cloned().collect())
+        match &self.shared {
+            Some(sink) => sink.lock().unwrap().clone(),
+            None => self.instance().records.borrow().iter().cloned().collect(),
+        }
+    }
+
+    /// The per-thread capture state this instance owns. Only valid to call
+    /// when `self.shared` is `None`.
+    fn instance(&self) -> &Instance {
+        self.instance
+            .as_ref()
+            .expect("non-shared CapLog always has a per-thread instance")
     }
 
     pub fn find<F>(&self, matcher: F) -> Vec<CapRecord>
     where
         F: Fn(&CapRecord) -> bool,
     {
-        LOG_RECORDS.with(|records| {
-            records
-                .borrow()
-                .iter()
-                .filter(|r| matcher(r))
-                .cloned()
-                .collect()
-        })
+        self.get_all().into_iter().filter(|r| matcher(r)).collect()
+    }
+
+    pub fn find_by_field(&self, key: &str, value: &str) -> Vec<CapRecord> {
+        self.find(|r| r.field(key) == Some(value))
+    }
+
+    /// Returns all records captured at or after the given instant.
+    pub fn find_since(&self, not_before: Instant) -> Vec<CapRecord> {
+        self.find(|r| r.timestamp >= not_before)
+    }
+
+    pub fn filter(&self, filter: RecordFilter) -> Vec<CapRecord> {
+        let mut out = Vec::new();
+
+        for r in self.get_all() {
+            if filter.limit.is_some_and(|limit| out.len() >= limit) {
+                break;
+            }
+
+            if !filter.matches(&r) {
+                continue;
+            }
+
+            out.push(r);
+        }
+
+        out
     }
 
     pub fn clear(&mut self) {
-        LOG_RECORDS.with(|records| records.borrow_mut().clear())
+        match &self.shared {
+            Some(sink) => sink.lock().unwrap().clear(),
+            None => {
+                let instance = self.instance();
+                instance.records.borrow_mut().clear();
+                instance.byte_total.set(0);
+            }
+        }
+    }
+
+    /// Polls the capture buffer until at least `n` records have been
+    /// captured, or returns `Err` once `timeout` elapses.
+    pub fn wait_for_count(&self, n: usize, timeout: Duration) -> Result<Vec<CapRecord>, TimeoutError> {
+        let start = Instant::now();
+
+        loop {
+            let records = self.get_all();
+            if records.len() >= n {
+                return Ok(records);
+            }
+
+            if start.elapsed() >= timeout {
+                return Err(TimeoutError);
+            }
+
+            thread::sleep(POLL_INTERVAL);
+        }
+    }
+
+    /// Polls the capture buffer until a record matches `matcher`, or returns
+    /// `Err` once `timeout` elapses.
+    pub fn wait_for<F>(&self, matcher: F, timeout: Duration) -> Result<Vec<CapRecord>, TimeoutError>
+    where
+        F: Fn(&CapRecord) -> bool,
+    {
+        let start = Instant::now();
+
+        loop {
+            let records = self.find(&matcher);
+            if !records.is_empty() {
+                return Ok(records);
+            }
+
+            if start.elapsed() >= timeout {
+                return Err(TimeoutError);
+            }
+
+            thread::sleep(POLL_INTERVAL);
+        }
+    }
+
+    /// Async counterpart to [`CapLog::wait_for_count`], for use inside
+    /// `#[tokio::test]`.
+    #[cfg(feature = "tokio")]
+    pub async fn wait_for_count_async(
+        &self,
+        n: usize,
+        timeout: Duration,
+    ) -> Result<Vec<CapRecord>, TimeoutError> {
+        let start = Instant::now();
+
+        loop {
+            let records = self.get_all();
+            if records.len() >= n {
+                return Ok(records);
+            }
+
+            if start.elapsed() >= timeout {
+                return Err(TimeoutError);
+            }
+
+            tokio::time::sleep(POLL_INTERVAL).await;
+        }
+    }
+
+    /// Async counterpart to [`CapLog::wait_for`], for use inside
+    /// `#[tokio::test]`.
+    #[cfg(feature = "tokio")]
+    pub async fn wait_for_async<F>(
+        &self,
+        matcher: F,
+        timeout: Duration,
+    ) -> Result<Vec<CapRecord>, TimeoutError>
+    where
+        F: Fn(&CapRecord) -> bool,
+    {
+        let start = Instant::now();
+
+        loop {
+            let records = self.find(&matcher);
+            if !records.is_empty() {
+                return Ok(records);
+            }
+
+            if start.elapsed() >= timeout {
+                return Err(TimeoutError);
+            }
+
+            tokio::time::sleep(POLL_INTERVAL).await;
+        }
     }
 }
 
 impl Drop for CapLog {
     fn drop(&mut self) {
-        self.clear()
+        self.clear();
+
+        match &self.shared {
+            Some(_) => SHARED_SINK.with(|s| *s.borrow_mut() = None),
+            None => {
+                let instance = self
+                    .instance
+                    .as_ref()
+                    .expect("non-shared CapLog always has a per-thread instance");
+                INSTANCES.with(|instances| {
+                    instances.borrow_mut().retain(|i| !Rc::ptr_eq(i, instance));
+                });
+            }
+        }
     }
 }
 
 #[cfg(test)]
 mod test_caplog {
-    use super::{CapLog, CapRecord, LOG_RECORDS};
+    use super::{CapLog, CapRecord, RecordFilter, TimeoutError};
 
     use log::{debug, error, info, trace, warn, Level};
-    use std::{thread, time::Duration};
+    use regex::Regex;
+    use std::{
+        thread,
+        time::{Duration, Instant},
+    };
 
     #[test]
     fn test_logs_are_cleared_when_caplog_goes_out_of_scope() {
         {
-            let _c = CapLog::new();
+            let c = CapLog::new();
 
             info!("foobar");
-            assert_eq!(LOG_RECORDS.with(|records| (records.borrow()).len()), 1);
+            assert_eq!(c.get_all().len(), 1);
 
             info!("baz");
-            assert_eq!(LOG_RECORDS.with(|records| (records.borrow()).len()), 2);
+            assert_eq!(c.get_all().len(), 2);
         }
 
-        assert_eq!(LOG_RECORDS.with(|records| (records.borrow()).len()), 0);
+        let c = CapLog::new();
+        assert_eq!(c.get_all().len(), 0);
     }
 
     #[test]
     fn test_captured_logs_are_not_shared_between_threads() {
         for _ in 0..16 {
             thread::spawn(|| {
-                let _c = CapLog::new();
+                let c = CapLog::new();
 
                 info!("foobar");
-                assert_eq!(LOG_RECORDS.with(|records| (records.borrow()).len()), 1);
+                assert_eq!(c.get_all().len(), 1);
 
                 thread::sleep(Duration::from_millis(5));
 
                 info!("baz");
-                assert_eq!(LOG_RECORDS.with(|records| (records.borrow()).len()), 2);
+                assert_eq!(c.get_all().len(), 2);
             });
         }
     }
 
+    #[test]
+    fn test_shared_capture_collects_logs_from_spawned_threads() {
+        let c = CapLog::new_shared();
+
+        let handles: Vec<_> = (0..4)
+            .map(|i| c.spawn(move || info!("from thread {i}")))
+            .collect();
+
+        for h in handles {
+            h.join().unwrap();
+        }
+
+        assert_eq!(c.find(|_| true).len(), 4);
+
+        c.spawn(|| {
+            thread::sleep(Duration::from_millis(20));
+            info!("delayed");
+        });
+
+        let res = c.wait_for(|r| r.msg == "delayed", Duration::from_secs(1));
+
+        assert!(res.is_ok());
+    }
+
+    #[test]
+    fn test_shared_capture_does_not_leak_into_other_threads() {
+        let c = CapLog::new_shared();
+
+        thread::spawn(|| {
+            let _other = CapLog::new();
+            info!("not part of the shared session");
+        })
+        .join()
+        .unwrap();
+
+        assert_eq!(c.find(|_| true).len(), 0);
+    }
+
+    #[test]
+    fn test_wait_for_count_returns_once_enough_records_are_captured() {
+        let c = CapLog::new();
+
+        info!("foobar");
+
+        let res = c.wait_for_count(1, Duration::from_secs(1));
+
+        assert_eq!(res.unwrap()[0].msg, "foobar");
+    }
+
+    #[test]
+    fn test_wait_for_count_times_out_when_not_enough_records_show_up() {
+        let c = CapLog::new();
+
+        let res = c.wait_for_count(1, Duration::from_millis(50));
+
+        assert_eq!(res, Err(TimeoutError));
+    }
+
+    #[test]
+    fn test_wait_for_returns_once_a_matching_record_is_captured() {
+        let c = CapLog::new();
+
+        info!("foobar");
+        info!("baz");
+
+        let res = c.wait_for(|r| r.msg == "baz", Duration::from_secs(1));
+
+        assert_eq!(res.unwrap()[0].msg, "baz");
+    }
+
+    #[test]
+    fn test_wait_for_times_out_when_no_record_matches() {
+        let c = CapLog::new();
+
+        info!("foobar");
+
+        let res = c.wait_for(|r| r.msg == "baz", Duration::from_millis(50));
+
+        assert_eq!(res, Err(TimeoutError));
+    }
+
     #[test]
     fn test_message_contains_the_formatted_message() {
         let c = CapLog::new();
@@ -153,10 +809,38 @@ mod test_caplog {
                 line: Some(line),
                 module_path: Some(module_path!().to_string()),
                 file: Some(file!().to_string()),
+                fields: Vec::new(),
+                timestamp: std::time::Instant::now(),
             }
         )
     }
 
+    #[test]
+    fn test_structured_fields_are_captured() {
+        let c = CapLog::new();
+
+        info!(request_id = 42, user = "alice"; "handled request");
+
+        let record = &c.find(|_| true)[0];
+
+        assert!(record.has_field("request_id"));
+        assert_eq!(record.field("request_id"), Some("42"));
+        assert_eq!(record.field("user"), Some("alice"));
+        assert_eq!(record.field("missing"), None);
+    }
+
+    #[test]
+    fn test_find_by_field_returns_matching_records() {
+        let c = CapLog::new();
+
+        info!(request_id = 42; "first");
+        info!(request_id = 43; "second");
+
+        let res = c.find_by_field("request_id", "42");
+        assert_eq!(res.len(), 1);
+        assert_eq!(res[0].msg, "first");
+    }
+
     #[test]
     fn test_all_levels_are_captured() {
         let c = CapLog::new();
@@ -217,6 +901,142 @@ mod test_caplog {
         assert_eq!(res[1].msg, "baz");
     }
 
+    #[test]
+    fn test_filter_by_level_keeps_records_at_or_above_the_threshold() {
+        let c = CapLog::new();
+
+        trace!("foo");
+        debug!("foo");
+        info!("foo");
+        warn!("foo");
+        error!("foo");
+
+        let res = c.filter(RecordFilter::new().level(Level::Warn));
+
+        assert_eq!(res.len(), 2);
+        assert!(res.iter().all(|r| r.level <= Level::Warn));
+    }
+
+    #[test]
+    fn test_filter_by_target_keeps_matching_records() {
+        let c = CapLog::new();
+
+        info!(target: "wanted", "foo");
+        info!(target: "other", "bar");
+
+        let res = c.filter(RecordFilter::new().target("wanted"));
+
+        assert_eq!(res.len(), 1);
+        assert_eq!(res[0].msg, "foo");
+    }
+
+    #[test]
+    fn test_filter_by_regex_keeps_matching_records() {
+        let c = CapLog::new();
+
+        info!("request 42 failed");
+        info!("all good");
+
+        let res = c.filter(RecordFilter::new().regex(Regex::new(r"request \d+").unwrap()));
+
+        assert_eq!(res.len(), 1);
+        assert_eq!(res[0].msg, "request 42 failed");
+    }
+
+    #[test]
+    fn test_filter_limit_stops_collecting_once_reached() {
+        let c = CapLog::new();
+
+        for _ in 0..5 {
+            info!("foo");
+        }
+
+        let res = c.filter(RecordFilter::new().limit(2));
+
+        assert_eq!(res.len(), 2);
+    }
+
+    #[test]
+    fn test_find_since_only_returns_records_captured_after_the_given_instant() {
+        let c = CapLog::new();
+
+        info!("before");
+
+        let cutoff = Instant::now();
+
+        info!("after");
+
+        let res = c.find_since(cutoff);
+
+        assert_eq!(res.len(), 1);
+        assert_eq!(res[0].msg, "after");
+    }
+
+    #[test]
+    fn test_filter_not_before_only_returns_records_captured_after_the_given_instant() {
+        let c = CapLog::new();
+
+        info!("before");
+
+        let cutoff = Instant::now();
+
+        info!("after");
+
+        let res = c.filter(RecordFilter::new().not_before(cutoff));
+
+        assert_eq!(res.len(), 1);
+        assert_eq!(res[0].msg, "after");
+    }
+
+    #[test]
+    fn test_with_capacity_evicts_oldest_records_fifo() {
+        let c = CapLog::with_capacity(2);
+
+        info!("one");
+        info!("two");
+        info!("three");
+
+        let res = c.get_all();
+
+        assert_eq!(res.len(), 2);
+        assert_eq!(res[0].msg, "two");
+        assert_eq!(res[1].msg, "three");
+    }
+
+    #[test]
+    fn test_overlapping_capacity_bounded_instances_do_not_clobber_each_other() {
+        let a = CapLog::with_capacity(2);
+        let _b = CapLog::with_capacity(5);
+
+        for _ in 0..5 {
+            info!("x");
+        }
+
+        assert_eq!(a.get_all().len(), 2);
+    }
+
+    #[test]
+    fn test_with_byte_capacity_evicts_oldest_records_once_budget_exceeded() {
+        let probe = CapLog::new();
+        info!("x");
+        let sample = probe.get_all().remove(0);
+        let single_record_size = sample.target.len()
+            + sample.msg.len()
+            + sample.module_path.map_or(0, |s| s.len())
+            + sample.file.map_or(0, |s| s.len());
+        drop(probe);
+
+        let c = CapLog::with_byte_capacity(single_record_size + 80);
+
+        info!("one");
+        info!("two");
+
+        let res = c.get_all();
+
+        assert_eq!(res.len(), 1);
+        assert_eq!(res[0].msg, "two");
+    }
+
     #[test]
     fn test_clear_resets_the_captured_logs() {
         let mut c = CapLog::new();
@@ -232,4 +1052,92 @@ mod test_caplog {
         info!("foobar");
         assert_eq!(c.find(|_| true).len(), 1);
     }
+
+    #[test]
+    fn test_builder_filters_out_records_below_the_global_level() {
+        let c = CapLog::builder().level(log::LevelFilter::Warn).build();
+
+        debug!("foo");
+        info!("foo");
+        warn!("foo");
+        error!("foo");
+
+        assert_eq!(c.get_all().len(), 2);
+    }
+
+    #[test]
+    fn test_builder_target_level_overrides_the_global_level() {
+        let c = CapLog::builder()
+            .level(log::LevelFilter::Trace)
+            .target_level(module_path!(), log::LevelFilter::Off)
+            .build();
+
+        info!("foo");
+
+        assert_eq!(c.get_all().len(), 0);
+    }
+
+    #[test]
+    fn test_default_capture_is_unaffected_by_a_previous_builder_configuration() {
+        {
+            let _c = CapLog::builder().level(log::LevelFilter::Error).build();
+        }
+
+        let c = CapLog::new();
+
+        info!("foo");
+
+        assert_eq!(c.get_all().len(), 1);
+    }
+
+    #[test]
+    fn test_overlapping_instances_do_not_clobber_each_others_level_filter() {
+        let filtered = CapLog::builder().level(log::LevelFilter::Warn).build();
+        let _plain = CapLog::new();
+
+        debug!("leaks through");
+
+        assert_eq!(filtered.get_all().len(), 0);
+    }
+
+    #[cfg(feature = "tokio")]
+    #[tokio::test]
+    async fn test_wait_for_count_async_returns_once_enough_records_are_captured() {
+        let c = CapLog::new();
+
+        info!("foobar");
+
+        let res = c.wait_for_count_async(1, Duration::from_secs(1)).await;
+
+        assert_eq!(res.unwrap()[0].msg, "foobar");
+    }
+
+    #[cfg(feature = "tokio")]
+    #[tokio::test]
+    async fn test_wait_for_async_returns_once_a_matching_record_is_captured() {
+        let c = CapLog::new();
+
+        info!("foobar");
+        info!("baz");
+
+        let res = c
+            .wait_for_async(|r| r.msg == "baz", Duration::from_secs(1))
+            .await;
+
+        assert_eq!(res.unwrap()[0].msg, "baz");
+    }
+
+    #[cfg(feature = "tokio")]
+    #[tokio::test]
+    async fn test_wait_for_async_times_out_when_no_record_matches() {
+        let c = CapLog::new();
+
+        info!("foobar");
+
+        let res = c
+            .wait_for_async(|r| r.msg == "baz", Duration::from_millis(50))
+            .await;
+
+        assert_eq!(res, Err(TimeoutError));
+    }
 }
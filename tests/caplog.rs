@@ -20,6 +20,8 @@ fn test_caplog_find() {
             module_path: Some(module_path!().to_string()),
             file: Some(file!().to_string()),
             line: Some(line + 1),
+            fields: Vec::new(),
+            timestamp: std::time::Instant::now(),
         }]
     );
 }
@@ -48,6 +50,8 @@ fn test_caplog_get_all() {
                 module_path: Some(module_path!().to_string()),
                 file: Some(file!().to_string()),
                 line: Some(line + 1),
+                fields: Vec::new(),
+                timestamp: std::time::Instant::now(),
             },
             CapRecord {
                 level: Level::Debug,
@@ -56,6 +60,8 @@ fn test_caplog_get_all() {
                 module_path: Some(module_path!().to_string()),
                 file: Some(file!().to_string()),
                 line: Some(line + 2),
+                fields: Vec::new(),
+                timestamp: std::time::Instant::now(),
             },
         ]
     );